@@ -1,18 +1,24 @@
 use std::{
+    cell::Cell,
     env, fs,
     io::Cursor,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process,
+    time::Instant,
 };
 
 use curl::easy::Easy;
 use libc;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use tar::Archive;
 use xz2::read::XzDecoder;
 
+const DEFAULT_KEEP: usize = 1;
+
 #[derive(Deserialize)]
 struct ReleaseAsset {
+    name: String,
     browser_download_url: String,
 }
 
@@ -27,6 +33,14 @@ fn geteuid() -> u32 {
 }
 
 fn download(url: &str, user_agent: Option<&str>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    download_impl(url, user_agent, false)
+}
+
+fn download_with_progress(url: &str, user_agent: Option<&str>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    download_impl(url, user_agent, true)
+}
+
+fn download_impl(url: &str, user_agent: Option<&str>, show_progress: bool) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
     let mut data = Vec::new();
     let mut easy = Easy::new();
     easy.url(url)?;
@@ -34,29 +48,407 @@ fn download(url: &str, user_agent: Option<&str>) -> Result<Vec<u8>, Box<dyn std:
         easy.useragent(ua)?;
     }
     easy.follow_location(true)?;
+
+    let start = Instant::now();
+    let last_print = Cell::new(Instant::now() - std::time::Duration::from_secs(1));
+
+    if show_progress {
+        easy.progress(true)?;
+    }
     {
         let mut transfer = easy.transfer();
         transfer.write_function(|new_data| {
             data.extend_from_slice(new_data);
             Ok(new_data.len())
         })?;
+        if show_progress {
+            transfer.progress_function(move |dltotal, dlnow, _, _| {
+                if dltotal <= 0.0 || dlnow <= 0.0 {
+                    return true;
+                }
+                let now = Instant::now();
+                if now.duration_since(last_print.get()).as_millis() < 200 && dlnow < dltotal {
+                    return true;
+                }
+                last_print.set(now);
+
+                let elapsed = start.elapsed().as_secs_f64().max(0.001);
+                let rate_mb_s = (dlnow / 1_048_576.0) / elapsed;
+                let pct = (dlnow / dltotal) * 100.0;
+                print!(
+                    "\r[↓] {:>5.1}%  {:.1}/{:.1} MiB  {:.2} MiB/s",
+                    pct,
+                    dlnow / 1_048_576.0,
+                    dltotal / 1_048_576.0,
+                    rate_mb_s
+                );
+                let _ = std::io::Write::flush(&mut std::io::stdout());
+                if dlnow >= dltotal {
+                    println!();
+                }
+                true
+            })?;
+        }
         transfer.perform()?;
     }
     Ok(data)
 }
 
+fn sha256_digest(data: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hasher.finalize().to_vec()
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn hex_decode(s: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if !s.len().is_multiple_of(2) {
+        return Err(format!("Odd-length hex string: {s}").into());
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| format!("Invalid hex digit in: {s}").into()))
+        .collect()
+}
+
+/// Fixed-time comparison so a checksum mismatch can't be timed byte-by-byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex_encode(&sha256_digest(data))
+}
+
+fn verify_checksum(assets: &[ReleaseAsset], asset_name: &str, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    let sidecar = assets.iter().find(|a| {
+        a.browser_download_url.ends_with(&format!("{asset_name}.sha256"))
+            || a.browser_download_url.ends_with(&format!("{asset_name}.sha256sum"))
+    });
+
+    let sidecar = match sidecar {
+        Some(a) => a,
+        None => {
+            eprintln!("[!] No checksum file found for {asset_name}, proceeding without verification");
+            return Ok(());
+        }
+    };
+
+    println!("[*] Verifying checksum: {}", sidecar.name);
+    let checksum_bytes = download(&sidecar.browser_download_url, None)?;
+    let checksum_text = String::from_utf8(checksum_bytes)?;
+    let expected_hex = checksum_text
+        .split_whitespace()
+        .next()
+        .ok_or("Empty checksum file")?
+        .to_lowercase();
+    let expected = hex_decode(&expected_hex)?;
+
+    let actual = sha256_digest(data);
+    if !constant_time_eq(&actual, &expected) {
+        return Err(format!(
+            "Checksum mismatch for {asset_name}: expected {expected_hex}, got {}",
+            hex_encode(&actual)
+        )
+        .into());
+    }
+
+    println!("[✓] Checksum verified");
+    Ok(())
+}
+
+fn cache_dir(home: &str) -> PathBuf {
+    PathBuf::from(home).join(".cache/protonup-cachyos")
+}
+
+fn cached_archive_path(cache_dir: &Path, install_name: &str) -> PathBuf {
+    cache_dir.join(format!("{install_name}.tar.xz"))
+}
+
+fn cached_checksum_path(cache_dir: &Path, install_name: &str) -> PathBuf {
+    cache_dir.join(format!("{install_name}.tar.xz.sha256"))
+}
+
+/// Writes the cached archive alongside our own SHA-256 sidecar so cache hits can be
+/// validated even for variants whose upstream releases don't publish a checksum.
+fn write_cached_archive(cache_dir: &Path, install_name: &str, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(cached_archive_path(cache_dir, install_name), data)?;
+    fs::write(cached_checksum_path(cache_dir, install_name), sha256_hex(data))?;
+    Ok(())
+}
+
+fn cached_archive_is_valid(cache_dir: &Path, install_name: &str) -> bool {
+    let Ok(data) = fs::read(cached_archive_path(cache_dir, install_name)) else {
+        return false;
+    };
+    let Ok(expected_hex) = fs::read_to_string(cached_checksum_path(cache_dir, install_name)) else {
+        return false;
+    };
+    let Ok(expected) = hex_decode(expected_hex.trim()) else {
+        return false;
+    };
+
+    constant_time_eq(&sha256_digest(&data), &expected)
+}
+
+/// Name (with ".tar.xz" stripped) of the newest cached archive for `prefix` that still
+/// passes our own checksum sidecar, ignoring anything corrupted, truncated, missing one,
+/// or belonging to a different `Variant`.
+fn newest_cached_archive(cache_dir: &Path, prefix: &str) -> Option<String> {
+    fs::read_dir(cache_dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.file_name().and_then(|n| n.to_str()).map_or(false, |n| n.ends_with(".tar.xz")))
+        .filter_map(|p| {
+            let install_name = p.file_name()?.to_str()?.strip_suffix(".tar.xz")?.to_string();
+            let modified = fs::metadata(&p).and_then(|m| m.modified()).ok();
+            Some((install_name, modified))
+        })
+        .filter(|(install_name, _)| install_name.starts_with(prefix))
+        .filter(|(install_name, _)| cached_archive_is_valid(cache_dir, install_name))
+        .max_by_key(|(_, modified)| *modified)
+        .map(|(install_name, _)| install_name)
+}
+
+/// Installed build directories under `prefix`, beyond the newest `keep`, newest first.
+fn stale_builds(install_dir: &str, prefix: &str, keep: usize) -> Vec<PathBuf> {
+    let mut builds: Vec<PathBuf> = fs::read_dir(install_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_dir()
+                && p.file_name()
+                    .and_then(|n| n.to_str())
+                    .map_or(false, |n| n.starts_with(prefix))
+        })
+        .collect();
+
+    builds.sort_by_key(|p| std::cmp::Reverse(fs::metadata(p).and_then(|m| m.modified()).ok()));
+    builds.into_iter().skip(keep).collect()
+}
+
+enum Mode {
+    List { variant: Variant },
+    Install { tag: Option<String>, keep: Option<usize>, variant: Variant, app: App },
+}
+
+fn parse_args() -> Result<Mode, Box<dyn std::error::Error>> {
+    let mut tag = None;
+    let mut keep = None;
+    let mut list = false;
+    let mut variant = Variant::ProtonCachyOS;
+    let mut app = App::Steam;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--list" => list = true,
+            "--version" | "--install" => {
+                tag = Some(args.next().ok_or("--version/--install requires a tag argument")?);
+            }
+            "--keep" => {
+                let n = args.next().ok_or("--keep requires a number argument")?;
+                keep = Some(n.parse::<usize>().map_err(|_| format!("Invalid --keep value: {n}"))?);
+            }
+            "--variant" => {
+                let v = args.next().ok_or("--variant requires a value")?;
+                variant = Variant::from_flag(&v)?;
+            }
+            "--app" => {
+                let a = args.next().ok_or("--app requires a value")?;
+                app = App::from_flag(&a)?;
+            }
+            other => return Err(format!("Unknown argument: {other}").into()),
+        }
+    }
+
+    if list {
+        Ok(Mode::List { variant })
+    } else {
+        Ok(Mode::Install { tag, keep, variant, app })
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct Config {
+    keep: Option<usize>,
+}
+
+fn config_path(home: &str) -> PathBuf {
+    PathBuf::from(home).join(".config/protonup-cachyos/config.toml")
+}
+
+fn load_config(home: &str) -> Config {
+    fs::read_to_string(config_path(home))
+        .ok()
+        .and_then(|s| toml::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn list_releases(variant: Variant, arch: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let releases_url = format!("https://api.github.com/repos/{}/releases", variant.repo());
+    let json_bytes = download(&releases_url, Some("protonup-cachyos"))?;
+    let releases: Vec<Release> = serde_json::from_slice(&json_bytes)?;
+
+    println!("Available {} releases ({arch}):", variant.display_name());
+    for release in &releases {
+        match variant.select_asset(&release.assets, arch) {
+            Some((asset, level)) if level == arch => println!("  {:<24} {}", release.tag_name, asset.name),
+            Some((asset, level)) => println!("  {:<24} {} (falls back to {level})", release.tag_name, asset.name),
+            None => println!("  {:<24} (no compatible build)", release.tag_name),
+        }
+    }
+    Ok(())
+}
+
+fn parse_cpu_flags(cpuinfo: &str) -> Vec<String> {
+    cpuinfo
+        .lines()
+        .find(|l| l.starts_with("flags"))
+        .and_then(|l| l.split_once(':'))
+        .map(|(_, flags)| flags.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+fn cpu_flags() -> Vec<String> {
+    fs::read_to_string("/proc/cpuinfo")
+        .ok()
+        .map(|content| parse_cpu_flags(&content))
+        .unwrap_or_default()
+}
+
+fn classify_arch(flags: &[String]) -> &'static str {
+    let has = |f: &str| flags.iter().any(|x| x == f);
+
+    let v2 = ["cx16", "lahf_lm", "popcnt", "sse4_1", "sse4_2", "ssse3"]
+        .iter()
+        .all(|f| has(f));
+    let v3 = v2
+        && ["avx", "avx2", "bmi1", "bmi2", "f16c", "fma", "movbe"]
+            .iter()
+            .all(|f| has(f));
+    let v4 = v3
+        && ["avx512f", "avx512bw", "avx512cd", "avx512dq", "avx512vl"]
+            .iter()
+            .all(|f| has(f));
+
+    if v4 {
+        "x86_64_v4"
+    } else if v3 {
+        "x86_64_v3"
+    } else if v2 {
+        "x86_64_v2"
+    } else {
+        "x86_64"
+    }
+}
+
 fn detect_arch() -> &'static str {
-    if let Ok(flags) = fs::read_to_string("/proc/cpuinfo") {
-        if flags.lines()
-            .find(|l| l.starts_with("flags"))
-            .map_or(false, |f| {
-                f.contains("avx2") && f.contains("bmi1") && f.contains("bmi2") && f.contains("fma")
-            })
-        {
-            return "x86_64_v3";
+    classify_arch(&cpu_flags())
+}
+
+fn arch_fallback_chain(arch: &str) -> &'static [&'static str] {
+    match arch {
+        "x86_64_v4" => &["x86_64_v4", "x86_64_v3", "x86_64_v2", "x86_64"],
+        "x86_64_v3" => &["x86_64_v3", "x86_64_v2", "x86_64"],
+        "x86_64_v2" => &["x86_64_v2", "x86_64"],
+        _ => &["x86_64"],
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Variant {
+    ProtonCachyOS,
+    GEProton,
+    WineGE,
+}
+
+impl Variant {
+    fn from_flag(s: &str) -> Result<Variant, Box<dyn std::error::Error>> {
+        match s {
+            "proton-cachyos" => Ok(Variant::ProtonCachyOS),
+            "ge-proton" => Ok(Variant::GEProton),
+            "wine-ge" => Ok(Variant::WineGE),
+            other => Err(format!("Unknown variant: {other}").into()),
+        }
+    }
+
+    fn repo(&self) -> &'static str {
+        match self {
+            Variant::ProtonCachyOS => "CachyOS/proton-cachyos",
+            Variant::GEProton => "GloriousEggroll/proton-ge-custom",
+            Variant::WineGE => "GloriousEggroll/wine-ge-custom",
+        }
+    }
+
+    fn display_name(&self) -> &'static str {
+        match self {
+            Variant::ProtonCachyOS => "proton-cachyos",
+            Variant::GEProton => "GE-Proton",
+            Variant::WineGE => "Wine-GE",
+        }
+    }
+
+    /// Prefix used to recognize this variant's installed directories for retention cleanup.
+    fn install_prefix(&self) -> &'static str {
+        match self {
+            Variant::ProtonCachyOS => "proton-cachyos-",
+            Variant::GEProton => "GE-Proton",
+            Variant::WineGE => "lutris-GE-Proton",
+        }
+    }
+
+    /// proton-cachyos publishes per-microarch assets; GE-Proton/Wine-GE ship one generic build.
+    fn select_asset<'a>(&self, assets: &'a [ReleaseAsset], arch: &str) -> Option<(&'a ReleaseAsset, &'static str)> {
+        match self {
+            Variant::ProtonCachyOS => arch_fallback_chain(arch).iter().find_map(|&level| {
+                assets
+                    .iter()
+                    .find(|a| a.browser_download_url.ends_with(&format!("{level}.tar.xz")))
+                    .map(|a| (a, level))
+            }),
+            Variant::GEProton | Variant::WineGE => assets
+                .iter()
+                .find(|a| a.browser_download_url.ends_with(".tar.xz"))
+                .map(|a| (a, "generic")),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum App {
+    Steam,
+    Lutris,
+}
+
+impl App {
+    fn from_flag(s: &str) -> Result<App, Box<dyn std::error::Error>> {
+        match s {
+            "steam" => Ok(App::Steam),
+            "lutris" => Ok(App::Lutris),
+            other => Err(format!("Unknown app: {other}").into()),
+        }
+    }
+
+    /// Candidate install roots for this app, in preference order.
+    fn install_dirs(&self, home: &str) -> Vec<String> {
+        match self {
+            App::Steam => vec![
+                format!("{home}/.steam/root/compatibilitytools.d"),
+                format!("{home}/.local/share/Steam/compatibilitytools.d"),
+            ],
+            App::Lutris => vec![format!("{home}/.local/share/lutris/runners/wine")],
         }
     }
-    "x86_64"
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -65,11 +457,17 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         process::exit(1);
     }
 
+    let mode = parse_args()?;
+    let arch = detect_arch();
+
+    let (tag, keep_cli, variant, app) = match mode {
+        Mode::List { variant } => return list_releases(variant, arch),
+        Mode::Install { tag, keep, variant, app } => (tag, keep, variant, app),
+    };
+
     let home = env::var("HOME")?;
-    let paths = [
-        format!("{}/.steam/root/compatibilitytools.d", home),
-        format!("{}/.local/share/Steam/compatibilitytools.d", home),
-    ];
+    let keep = keep_cli.unwrap_or_else(|| load_config(&home).keep.unwrap_or(DEFAULT_KEEP)).max(1);
+    let paths = app.install_dirs(&home);
 
     let install_dir = paths.iter()
         .find(|p| PathBuf::from(p).exists())
@@ -77,35 +475,67 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     fs::create_dir_all(install_dir)?;
 
-    let arch = detect_arch();
+    let cache_dir = cache_dir(&home);
+    fs::create_dir_all(&cache_dir)?;
 
-    let (_tag, url) = {
-        let api_url = "https://api.github.com/repos/CachyOS/proton-cachyos/releases/latest";
-        let json_bytes = download(api_url, Some("protonup-cachyos"))?;
-        let release: Release = serde_json::from_slice(&json_bytes)?;
-        let asset_url = release.assets.iter()
-            .find(|a| a.browser_download_url.ends_with(&format!("{arch}.tar.xz")))
-            .ok_or("No matching asset found")?
-            .browser_download_url.clone();
-        (release.tag_name, asset_url)
+    let api_url = match &tag {
+        Some(tag) => format!("https://api.github.com/repos/{}/releases/tags/{tag}", variant.repo()),
+        None => format!("https://api.github.com/repos/{}/releases/latest", variant.repo()),
     };
+    let release = download(&api_url, Some("protonup-cachyos"))
+        .and_then(|b| serde_json::from_slice::<Release>(&b).map_err(Box::<dyn std::error::Error>::from));
 
-    let install_name = url.split('/').last().unwrap().strip_suffix(".tar.xz").unwrap();
-    let install_path = PathBuf::from(install_dir).join(install_name);
+    let (data, install_name) = match release {
+        Ok(release) => {
+            let (asset, level) = variant.select_asset(&release.assets, arch)
+                .ok_or("No matching asset found")?;
+            let url = asset.browser_download_url.clone();
 
-    if install_path.exists() {
-        println!("[✓] Already installed: {}", install_name);
-        return Ok(());
-    }
+            let install_name = url.split('/').last().unwrap().strip_suffix(".tar.xz").unwrap().to_string();
+            let install_path = PathBuf::from(install_dir).join(&install_name);
 
-    if arch == "x86_64_v3" {
-        println!("[*] CPU supports x86_64_v3 — using optimized build");
-    } else {
-        println!("[*] CPU does not support x86_64_v3 — using baseline x86_64 build");
-    }
+            if install_path.exists() {
+                println!("[✓] Already installed: {}", install_name);
+                return Ok(());
+            }
+
+            if level == arch {
+                println!("[*] CPU supports {level} — using optimized build");
+            } else {
+                println!("[*] CPU supports {arch}, but no such build was published — using {level}");
+            }
+
+            let asset_name = url.split('/').last().unwrap();
+
+            let data = if cached_archive_is_valid(&cache_dir, &install_name) {
+                println!("[*] Using cached archive: {}", asset_name);
+                fs::read(cached_archive_path(&cache_dir, &install_name))?
+            } else {
+                println!("[↓] Downloading: {}", asset_name);
+                let data = download_with_progress(&url, None)?;
+                verify_checksum(&release.assets, asset_name, &data)?;
+                write_cached_archive(&cache_dir, &install_name, &data)?;
+                data
+            };
 
-    println!("[↓] Downloading: {}", url.split('/').last().unwrap());
-    let data = download(&url, None)?;
+            (data, install_name)
+        }
+        Err(e) => {
+            eprintln!("[!] Could not reach GitHub API ({e}), falling back to cached archive");
+            let install_name = newest_cached_archive(&cache_dir, variant.install_prefix())
+                .ok_or("No valid cached archives for this variant available and GitHub API unreachable")?;
+
+            let install_path = PathBuf::from(install_dir).join(&install_name);
+            if install_path.exists() {
+                println!("[✓] Already installed: {}", install_name);
+                return Ok(());
+            }
+
+            println!("[*] Installing from cache (offline): {}", install_name);
+            (fs::read(cached_archive_path(&cache_dir, &install_name))?, install_name)
+        }
+    };
+    let install_path = PathBuf::from(install_dir).join(&install_name);
 
     println!("[>] Extracting...");
     let tmp_dir = env::temp_dir().join("proton_extract");
@@ -115,29 +545,235 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut archive = Archive::new(tar);
     archive.unpack(&tmp_dir)?;
 
-    let extracted_dir = fs::read_dir(&tmp_dir)?
-        .find_map(|e| {
-            let p = e.ok()?.path();
-            (p.is_dir() && p.file_name()?.to_str()?.starts_with("proton-")).then_some(p)
-        })
-        .ok_or("Extracted folder not found")?;
-
-    fs::rename(&extracted_dir, &install_path)?;
+    let candidates: Vec<PathBuf> = fs::read_dir(&tmp_dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect();
 
-    for entry in fs::read_dir(install_dir)? {
-        let p = entry?.path();
-        if p != install_path && p.is_dir() {
-            if let Some(name) = p.file_name().and_then(|n| n.to_str()) {
-                if name.starts_with("proton-cachyos-") {
-                    let _ = fs::remove_dir_all(p);
-                }
+    let extracted_dir = match candidates.len() {
+        0 => return Err("Extracted folder not found".into()),
+        1 => candidates.into_iter().next().unwrap(),
+        _ => {
+            let prefix = variant.install_prefix();
+            let mut matching = candidates.into_iter().filter(|p| {
+                p.file_name().and_then(|n| n.to_str()).map_or(false, |n| n.starts_with(prefix))
+            });
+            match (matching.next(), matching.next()) {
+                (Some(only), None) => only,
+                _ => return Err("Archive extracted more than one top-level directory; expected exactly one".into()),
             }
         }
+    };
+
+    fs::rename(&extracted_dir, &install_path)?;
+
+    for stale in stale_builds(install_dir, variant.install_prefix(), keep) {
+        let _ = fs::remove_dir_all(stale);
     }
 
     println!("[✓] Installed: {}", install_name);
-    println!("[✓] Done. Restart Steam to use the new version.");
+    let restart_hint = match app {
+        App::Steam => "Restart Steam",
+        App::Lutris => "Restart Lutris",
+    };
+    println!("[✓] Done. {restart_hint} to use the new version.");
 
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn asset(url: &str) -> ReleaseAsset {
+        ReleaseAsset {
+            name: url.rsplit('/').next().unwrap().to_string(),
+            browser_download_url: url.to_string(),
+        }
+    }
+
+    static TEST_DIR_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+    /// A fresh, empty scratch directory under the OS temp dir, unique per test.
+    fn temp_test_dir(tag: &str) -> PathBuf {
+        let n = TEST_DIR_COUNTER.fetch_add(1, Ordering::SeqCst);
+        let dir = env::temp_dir().join(format!("protonup-cachyos-test-{}-{tag}-{n}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn parse_cpu_flags_reads_the_flags_line() {
+        let cpuinfo = "processor\t: 0\nflags\t\t: fpu vme de avx2 bmi1\nbugs\t\t: spectre\n";
+        assert_eq!(parse_cpu_flags(cpuinfo), vec!["fpu", "vme", "de", "avx2", "bmi1"]);
+    }
+
+    #[test]
+    fn parse_cpu_flags_missing_line_is_empty() {
+        assert_eq!(parse_cpu_flags("processor\t: 0\n"), Vec::<String>::new());
+    }
+
+    fn flags(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    const V2: &[&str] = &["cx16", "lahf_lm", "popcnt", "sse4_1", "sse4_2", "ssse3"];
+    const V3_EXTRA: &[&str] = &["avx", "avx2", "bmi1", "bmi2", "f16c", "fma", "movbe"];
+    const V4_EXTRA: &[&str] = &["avx512f", "avx512bw", "avx512cd", "avx512dq", "avx512vl"];
+
+    #[test]
+    fn classify_arch_baseline_when_no_flags_present() {
+        assert_eq!(classify_arch(&flags(&["fpu", "vme"])), "x86_64");
+    }
+
+    #[test]
+    fn classify_arch_v2_requires_the_full_set() {
+        assert_eq!(classify_arch(&flags(&V2[..V2.len() - 1])), "x86_64");
+        assert_eq!(classify_arch(&flags(V2)), "x86_64_v2");
+    }
+
+    #[test]
+    fn classify_arch_v3_requires_v2_plus_extras() {
+        let v3: Vec<&str> = V2.iter().chain(V3_EXTRA).copied().collect();
+        assert_eq!(classify_arch(&flags(&v3)), "x86_64_v3");
+
+        let missing_one = flags(&v3[..v3.len() - 1]);
+        assert_eq!(classify_arch(&missing_one), "x86_64_v2");
+    }
+
+    #[test]
+    fn classify_arch_v4_requires_v3_plus_avx512() {
+        let v4: Vec<&str> = V2.iter().chain(V3_EXTRA).chain(V4_EXTRA).copied().collect();
+        assert_eq!(classify_arch(&flags(&v4)), "x86_64_v4");
+
+        let missing_one = flags(&v4[..v4.len() - 1]);
+        assert_eq!(classify_arch(&missing_one), "x86_64_v3");
+    }
+
+    #[test]
+    fn arch_fallback_chain_walks_down_to_baseline() {
+        assert_eq!(arch_fallback_chain("x86_64_v4"), &["x86_64_v4", "x86_64_v3", "x86_64_v2", "x86_64"]);
+        assert_eq!(arch_fallback_chain("x86_64_v2"), &["x86_64_v2", "x86_64"]);
+        assert_eq!(arch_fallback_chain("x86_64"), &["x86_64"]);
+        assert_eq!(arch_fallback_chain("bogus"), &["x86_64"]);
+    }
+
+    #[test]
+    fn select_asset_proton_cachyos_falls_back_to_lower_levels() {
+        let assets = vec![
+            asset("https://example.com/proton-cachyos-9.0-x86_64_v2.tar.xz"),
+            asset("https://example.com/proton-cachyos-9.0-x86_64.tar.xz"),
+        ];
+        let (chosen, level) = Variant::ProtonCachyOS.select_asset(&assets, "x86_64_v4").unwrap();
+        assert_eq!(level, "x86_64_v2");
+        assert!(chosen.browser_download_url.ends_with("x86_64_v2.tar.xz"));
+    }
+
+    #[test]
+    fn select_asset_proton_cachyos_none_when_nothing_matches() {
+        let assets = vec![asset("https://example.com/proton-cachyos-9.0-x86_64_v3.tar.xz")];
+        assert!(Variant::ProtonCachyOS.select_asset(&assets, "x86_64").is_none());
+    }
+
+    #[test]
+    fn select_asset_ge_proton_ignores_arch_and_picks_the_tarball() {
+        let assets = vec![
+            asset("https://example.com/GE-Proton9-7.sha512sum"),
+            asset("https://example.com/GE-Proton9-7.tar.xz"),
+        ];
+        let (chosen, level) = Variant::GEProton.select_asset(&assets, "x86_64_v3").unwrap();
+        assert_eq!(level, "generic");
+        assert!(chosen.browser_download_url.ends_with(".tar.xz"));
+    }
+
+    #[test]
+    fn constant_time_eq_compares_content_and_length() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"ab"));
+    }
+
+    #[test]
+    fn hex_decode_round_trips_hex_encode() {
+        let data = b"proton-cachyos";
+        let digest = sha256_digest(data);
+        assert_eq!(hex_decode(&hex_encode(&digest)).unwrap(), digest);
+    }
+
+    #[test]
+    fn cached_archive_is_valid_for_a_freshly_written_archive() {
+        let dir = temp_test_dir("cache-valid");
+        write_cached_archive(&dir, "proton-cachyos-9.0-x86_64", b"fake tarball bytes").unwrap();
+        assert!(cached_archive_is_valid(&dir, "proton-cachyos-9.0-x86_64"));
+    }
+
+    #[test]
+    fn cached_archive_is_invalid_when_bytes_are_tampered() {
+        let dir = temp_test_dir("cache-tampered");
+        write_cached_archive(&dir, "proton-cachyos-9.0-x86_64", b"fake tarball bytes").unwrap();
+        fs::write(cached_archive_path(&dir, "proton-cachyos-9.0-x86_64"), b"corrupted").unwrap();
+        assert!(!cached_archive_is_valid(&dir, "proton-cachyos-9.0-x86_64"));
+    }
+
+    #[test]
+    fn cached_archive_is_invalid_without_a_sidecar() {
+        let dir = temp_test_dir("cache-no-sidecar");
+        fs::write(cached_archive_path(&dir, "proton-cachyos-9.0-x86_64"), b"whatever").unwrap();
+        assert!(!cached_archive_is_valid(&dir, "proton-cachyos-9.0-x86_64"));
+    }
+
+    #[test]
+    fn newest_cached_archive_only_considers_the_matching_variant() {
+        let dir = temp_test_dir("cache-variant");
+        write_cached_archive(&dir, "GE-Proton9-7", b"ge-proton bytes").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        write_cached_archive(&dir, "proton-cachyos-9.0-x86_64", b"cachyos bytes").unwrap();
+
+        // Despite GE-Proton9-7 being older, it's the only one matching this prefix.
+        assert_eq!(
+            newest_cached_archive(&dir, Variant::GEProton.install_prefix()),
+            Some("GE-Proton9-7".to_string())
+        );
+        assert_eq!(
+            newest_cached_archive(&dir, Variant::ProtonCachyOS.install_prefix()),
+            Some("proton-cachyos-9.0-x86_64".to_string())
+        );
+        assert_eq!(newest_cached_archive(&dir, Variant::WineGE.install_prefix()), None);
+    }
+
+    #[test]
+    fn newest_cached_archive_skips_corrupted_entries() {
+        let dir = temp_test_dir("cache-corrupted");
+        write_cached_archive(&dir, "proton-cachyos-9.0-x86_64", b"good bytes").unwrap();
+        fs::write(cached_archive_path(&dir, "proton-cachyos-9.0-x86_64"), b"tampered after write").unwrap();
+        assert_eq!(newest_cached_archive(&dir, Variant::ProtonCachyOS.install_prefix()), None);
+    }
+
+    #[test]
+    fn stale_builds_keeps_only_the_newest_n() {
+        let dir = temp_test_dir("retention");
+        let dir_str = dir.to_str().unwrap();
+        for name in ["proton-cachyos-1.0-x86_64", "proton-cachyos-2.0-x86_64", "proton-cachyos-3.0-x86_64"] {
+            fs::create_dir_all(dir.join(name)).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        // An unrelated directory must never be touched by retention for this variant.
+        fs::create_dir_all(dir.join("GE-Proton9-7")).unwrap();
+
+        let stale = stale_builds(dir_str, "proton-cachyos-", 1);
+        let stale_names: Vec<_> = stale
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap().to_string())
+            .collect();
+
+        assert_eq!(stale_names.len(), 2);
+        assert!(stale_names.contains(&"proton-cachyos-1.0-x86_64".to_string()));
+        assert!(stale_names.contains(&"proton-cachyos-2.0-x86_64".to_string()));
+        assert!(!stale_names.contains(&"proton-cachyos-3.0-x86_64".to_string()));
+        assert!(!stale_names.contains(&"GE-Proton9-7".to_string()));
+    }
+}
+